@@ -0,0 +1,101 @@
+use std::time::Duration;
+use once_cell::sync::OnceCell;
+use opentelemetry::{global, KeyValue};
+use opentelemetry::metrics::{Counter, Histogram, Meter, UpDownCounter};
+use opentelemetry_otlp::WithExportConfig;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Configuration for the optional OTLP telemetry subsystem. Left unset (`otlp_endpoint: None`)
+/// in production deployments that don't run a collector, so `init` installs only the `fmt` layer.
+#[derive(Debug, Clone, Default)]
+pub struct TelemetryConfig {
+    pub otlp_endpoint: Option<String>,
+    pub sampling_ratio: f64
+}
+
+/// Installs an OTLP span exporter (when `config.otlp_endpoint` is set) as a `tracing` layer
+/// alongside the existing `fmt` layer, and registers the meters used by discovery and the TVM
+/// emulator. Safe to call once at process startup; when telemetry isn't configured it installs
+/// only the `fmt` layer, with no exporter and no meter provider.
+pub fn init(config: &TelemetryConfig) -> anyhow::Result<()> {
+    let Some(endpoint) = config.otlp_endpoint.as_ref() else {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer())
+            .try_init()?;
+
+        return Ok(());
+    };
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(
+            opentelemetry_sdk::trace::config()
+                .with_sampler(opentelemetry_sdk::trace::Sampler::TraceIdRatioBased(config.sampling_ratio))
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .try_init()?;
+
+    let exporter = opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint);
+    let meter_provider = opentelemetry_otlp::new_pipeline()
+        .metrics(opentelemetry_sdk::runtime::Tokio)
+        .with_exporter(exporter)
+        .with_period(Duration::from_secs(10))
+        .build()?;
+
+    global::set_meter_provider(meter_provider);
+    Metrics::init();
+
+    Ok(())
+}
+
+/// Lazily-initialized handles to the counters/histograms emitted by discovery and the TVM
+/// emulator, backed by whatever global meter provider `init` installed (or the default no-op
+/// one, if telemetry isn't configured).
+pub struct Metrics {
+    /// Current count of active liteservers. An `UpDownCounter` rather than a `Counter`: the
+    /// discovery loop adjusts it by +1/-1 on insert/remove, it does not re-report the full
+    /// count every tick.
+    pub liteservers_discovered: UpDownCounter<i64>,
+    pub liteservers_inserted: Counter<u64>,
+    pub liteservers_removed: Counter<u64>,
+    pub liteserver_call_latency: Histogram<f64>,
+    pub emulator_calls: Counter<u64>,
+    pub emulator_call_duration: Histogram<f64>,
+    pub emulator_gas_consumed: Histogram<u64>
+}
+
+static METRICS: OnceCell<Metrics> = OnceCell::new();
+
+impl Metrics {
+    fn init() {
+        let _ = METRICS.get_or_init(|| Metrics::new(global::meter("ton-grpc")));
+    }
+
+    fn new(meter: Meter) -> Self {
+        Self {
+            liteservers_discovered: meter.i64_up_down_counter("liteservers_discovered").init(),
+            liteservers_inserted: meter.u64_counter("liteservers_inserted").init(),
+            liteservers_removed: meter.u64_counter("liteservers_removed").init(),
+            liteserver_call_latency: meter.f64_histogram("liteserver_call_latency_seconds").init(),
+            emulator_calls: meter.u64_counter("tvm_emulator_calls").init(),
+            emulator_call_duration: meter.f64_histogram("tvm_emulator_call_duration_seconds").init(),
+            emulator_gas_consumed: meter.u64_histogram("tvm_emulator_gas_consumed").init()
+        }
+    }
+
+    /// Returns the global metrics handle, lazily falling back to the no-op meter provider if
+    /// `telemetry::init` was never called (e.g. telemetry disabled in config).
+    pub fn get() -> &'static Metrics {
+        METRICS.get_or_init(|| Metrics::new(global::meter("ton-grpc")))
+    }
+}
+
+pub fn method_attribute(method: &str) -> [KeyValue; 1] {
+    [KeyValue::new("method", method.to_string())]
+}
@@ -0,0 +1,66 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Ident, LitInt, LitStr};
+
+/// Derives `impl Requestable` for a plain TL request struct, so a new liteserver method can
+/// be added as `#[derive(Requestable)] #[requestable(response = "...")] struct Foo { .. }`
+/// instead of a hand-written trait block.
+///
+/// ```ignore
+/// #[derive(Serialize, Requestable)]
+/// #[requestable(response = "GetAccountStateResponse", timeout_ms = 5000)]
+/// struct GetAccountState {
+///     account_address: String
+/// }
+/// ```
+#[proc_macro_derive(Requestable, attributes(requestable))]
+pub fn derive_requestable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let ident = input.ident;
+    let span = ident.span();
+
+    let attr = input.attrs.iter()
+        .find(|attr| attr.path().is_ident("requestable"))
+        .ok_or_else(|| syn::Error::new(span, "#[derive(Requestable)] requires a `#[requestable(response = \"...\")]` attribute"))?;
+
+    let mut response: Option<Ident> = None;
+    let mut timeout_ms: Option<LitInt> = None;
+
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("response") {
+            let value: LitStr = meta.value()?.parse()?;
+            response = Some(value.parse()?);
+
+            Ok(())
+        } else if meta.path.is_ident("timeout_ms") {
+            timeout_ms = Some(meta.value()?.parse()?);
+
+            Ok(())
+        } else {
+            Err(meta.error("unsupported requestable attribute"))
+        }
+    })?;
+
+    let response = response.ok_or_else(|| syn::Error::new_spanned(attr, "#[requestable(..)] is missing `response = \"...\"`"))?;
+
+    let timeout_override = timeout_ms.map(|ms| quote! {
+        fn timeout(&self) -> std::time::Duration {
+            std::time::Duration::from_millis(#ms)
+        }
+    });
+
+    Ok(quote! {
+        #[async_trait::async_trait]
+        impl crate::request::Requestable for #ident {
+            type Response = #response;
+
+            #timeout_override
+        }
+    })
+}
@@ -0,0 +1,8 @@
+use tonlibjson_client_derive::Requestable;
+
+#[derive(Requestable)]
+struct GetFoo {
+    id: u64
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use tonlibjson_client_derive::Requestable;
+
+#[derive(Requestable)]
+#[requestable(timeout_ms = 1000)]
+struct GetFoo {
+    id: u64
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use tonlibjson_client_derive::Requestable;
+
+#[derive(Requestable)]
+#[requestable(response = "FooResponse", retries = 3)]
+struct GetFoo {
+    id: u64
+}
+
+fn main() {}
@@ -0,0 +1,150 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use anyhow::anyhow;
+use futures::FutureExt;
+use tokio::sync::Mutex as AsyncMutex;
+use tower::{Layer, Service};
+use crate::request::Request;
+
+/// Extracts the TL method name (the `@type` tag) a `Request` carries in its flattened `data`,
+/// so policy layers like [`FilterLayer`] and the rate limiter can key decisions off it.
+pub fn method_name(req: &Request) -> Option<&str> {
+    req.data.get("@type").and_then(|v| v.as_str())
+}
+
+/// Runs an async predicate over each `Request` before it reaches the inner service, rejecting
+/// disallowed or overly expensive TL methods up front instead of spending a liteserver round
+/// trip on them.
+pub struct FilterLayer<P> {
+    predicate: P
+}
+
+impl<P> FilterLayer<P> {
+    pub fn new(predicate: P) -> Self {
+        Self { predicate }
+    }
+}
+
+impl<S, P> Layer<S> for FilterLayer<P> where P : Clone {
+    type Service = FilterService<S, P>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        FilterService { inner: Arc::new(AsyncMutex::new(inner)), predicate: self.predicate.clone() }
+    }
+}
+
+// `inner` sits behind an async mutex, not a plain field, so `call` can await the predicate
+// before ever touching the inner service: `S::call` runs its synchronous side effects (e.g.
+// `SharedService`'s Peak-EWMA outstanding bump, `RateLimitService`'s token deduction) the
+// moment it's invoked, not when the returned future resolves, so it must not be invoked for a
+// request the predicate is about to reject.
+pub struct FilterService<S, P> {
+    inner: Arc<AsyncMutex<S>>,
+    predicate: P
+}
+
+impl<S, P> Clone for FilterService<S, P> where P : Clone {
+    fn clone(&self) -> Self {
+        Self { inner: Arc::clone(&self.inner), predicate: self.predicate.clone() }
+    }
+}
+
+impl<S, P, F> Service<Request> for FilterService<S, P>
+    where
+        S : Service<Request, Error = anyhow::Error> + Send + 'static, S::Future : Send + 'static,
+        P : Fn(&Request) -> F + Send,
+        F : Future<Output = anyhow::Result<()>> + Send + 'static {
+    type Response = S::Response;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.inner.try_lock() {
+            Ok(mut inner) => inner.poll_ready(cx),
+            Err(_) => {
+                cx.waker().wake_by_ref();
+
+                Poll::Pending
+            }
+        }
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let check = (self.predicate)(&req);
+        let inner = Arc::clone(&self.inner);
+
+        async move {
+            check.await?;
+
+            inner.lock().await.call(req).await
+        }.boxed()
+    }
+}
+
+/// Rejects a request whose TL method is in the given denylist. The predicate most callers want
+/// from [`FilterLayer::new`].
+pub fn deny_methods(denied: Vec<String>) -> impl Fn(&Request) -> futures::future::Ready<anyhow::Result<()>> + Clone {
+    move |req: &Request| {
+        let result = match method_name(req) {
+            Some(method) if denied.iter().any(|m| m == method) =>
+                Err(anyhow!("method {method} is not allowed")),
+            _ => Ok(())
+        };
+
+        futures::future::ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use tower::{Layer, Service, ServiceExt, service_fn};
+    use crate::request::Request;
+    use super::{deny_methods, FilterLayer};
+
+    #[tokio::test]
+    async fn rejects_denied_method() {
+        let inner = service_fn(|_req: Request| async { Ok::<_, anyhow::Error>(json!({})) });
+        let mut service = FilterLayer::new(deny_methods(vec!["liteServer.sendMessage".to_string()])).layer(inner);
+
+        let request = Request::with_timeout(json!({ "@type": "liteServer.sendMessage" }), Duration::from_secs(3)).unwrap();
+        let result = service.ready().await.unwrap().call(request).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn passes_through_allowed_method() {
+        let inner = service_fn(|_req: Request| async { Ok::<_, anyhow::Error>(json!({ "ok": true })) });
+        let mut service = FilterLayer::new(deny_methods(vec!["liteServer.sendMessage".to_string()])).layer(inner);
+
+        let request = Request::with_timeout(json!({ "@type": "liteServer.getAccountState" }), Duration::from_secs(3)).unwrap();
+        let result = service.ready().await.unwrap().call(request).await;
+
+        assert_eq!(result.unwrap(), json!({ "ok": true }));
+    }
+
+    #[tokio::test]
+    async fn rejected_request_never_reaches_inner_service() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let called = Arc::new(AtomicBool::new(false));
+        let called_ref = Arc::clone(&called);
+        let inner = service_fn(move |_req: Request| {
+            called_ref.store(true, Ordering::SeqCst);
+
+            async { Ok::<_, anyhow::Error>(json!({})) }
+        });
+        let mut service = FilterLayer::new(deny_methods(vec!["liteServer.sendMessage".to_string()])).layer(inner);
+
+        let request = Request::with_timeout(json!({ "@type": "liteServer.sendMessage" }), Duration::from_secs(3)).unwrap();
+        let result = service.ready().await.unwrap().call(request).await;
+
+        assert!(result.is_err());
+        assert!(!called.load(Ordering::SeqCst), "inner service must not run for a request the predicate rejects");
+    }
+}
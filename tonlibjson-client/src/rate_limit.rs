@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+use tower::{Layer, Service};
+use crate::filter::method_name;
+use crate::request::Request;
+
+struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    updated_at: Instant
+}
+
+impl TokenBucket {
+    fn new(qps: f64) -> Self {
+        Self { capacity: qps, tokens: qps, refill_per_sec: qps, updated_at: Instant::now() }
+    }
+
+    fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Per-TL-method token-bucket rate limiting, so a handful of expensive calls (e.g.
+/// `runGetMethod`, `liteServer.sendMessage`) can be capped at a lower QPS than cheap lookups
+/// without a single global limit punishing every method alike.
+pub struct RateLimitLayer {
+    default_qps: f64,
+    overrides: HashMap<String, f64>
+}
+
+impl RateLimitLayer {
+    pub fn new(default_qps: f64) -> Self {
+        Self { default_qps, overrides: HashMap::new() }
+    }
+
+    pub fn with_method_limit(mut self, method: impl Into<String>, qps: f64) -> Self {
+        self.overrides.insert(method.into(), qps);
+
+        self
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            default_qps: self.default_qps,
+            overrides: self.overrides.clone(),
+            buckets: Mutex::new(HashMap::new())
+        }
+    }
+}
+
+pub struct RateLimitService<S> {
+    inner: S,
+    default_qps: f64,
+    overrides: HashMap<String, f64>,
+    buckets: Mutex<HashMap<String, TokenBucket>>
+}
+
+impl<S> Service<Request> for RateLimitService<S>
+    where S : Service<Request, Error = anyhow::Error> + Send, S::Future : Send + 'static {
+    type Response = S::Response;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        use futures::FutureExt;
+
+        let key = method_name(&req).unwrap_or("unknown").to_string();
+        let qps = *self.overrides.get(&key).unwrap_or(&self.default_qps);
+
+        let allowed = {
+            let mut buckets = self.buckets.lock().unwrap();
+            buckets.entry(key.clone()).or_insert_with(|| TokenBucket::new(qps)).try_acquire()
+        };
+
+        if !allowed {
+            return async move { Err(anyhow!("rate limit exceeded for method {key}")) }.boxed();
+        }
+
+        self.inner.call(req).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use tower::{Layer, Service, ServiceExt, service_fn};
+    use crate::request::Request;
+    use super::RateLimitLayer;
+
+    #[tokio::test]
+    async fn enforces_per_method_qps() {
+        let inner = service_fn(|_req: Request| async { Ok::<_, anyhow::Error>(json!({})) });
+        let mut service = RateLimitLayer::new(100.0)
+            .with_method_limit("liteServer.sendMessage", 1.0)
+            .layer(inner);
+
+        let request = || Request::with_timeout(json!({ "@type": "liteServer.sendMessage" }), Duration::from_secs(3)).unwrap();
+
+        let first = service.ready().await.unwrap().call(request()).await;
+        let second = service.ready().await.unwrap().call(request()).await;
+
+        assert!(first.is_ok());
+        assert!(second.is_err());
+    }
+}
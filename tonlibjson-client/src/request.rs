@@ -6,6 +6,10 @@ use serde::de::DeserializeOwned;
 use serde_json::Value;
 use tower::{Service, ServiceExt};
 
+/// Can be implemented by hand, or generated via `#[derive(Requestable)]` from the
+/// `tonlibjson-client-derive` crate for the common case of a plain struct with a fixed
+/// response type and an optional fixed timeout, e.g.
+/// `#[requestable(response = "GetAccountStateResponse", timeout_ms = 5000)]`.
 #[async_trait]
 pub trait Requestable where Self : Serialize + Sized {
     type Response : DeserializeOwned;
@@ -86,9 +90,44 @@ impl Request {
 mod tests {
     use std::str::FromStr;
     use std::time::Duration;
+    use serde::{Deserialize, Serialize};
     use serde_json::json;
     use uuid::Uuid;
-    use crate::request::Request;
+    use tonlibjson_client_derive::Requestable;
+    use crate::request::{Request, Requestable};
+
+    #[derive(Serialize, Requestable)]
+    #[requestable(response = "GetFooResponse", timeout_ms = 1234)]
+    struct GetFoo {
+        id: u64
+    }
+
+    #[derive(Deserialize)]
+    struct GetFooResponse {
+        #[allow(dead_code)]
+        ok: bool
+    }
+
+    #[test]
+    fn derive_requestable_applies_the_configured_timeout() {
+        let req = GetFoo { id: 1 };
+
+        assert_eq!(req.timeout(), Duration::from_millis(1234));
+    }
+
+    #[test]
+    fn derive_requestable_defaults_timeout_when_unset() {
+        #[derive(Serialize, Requestable)]
+        #[requestable(response = "GetFooResponse")]
+        struct GetFooNoTimeout {
+            #[allow(dead_code)]
+            id: u64
+        }
+
+        let req = GetFooNoTimeout { id: 1 };
+
+        assert_eq!(req.timeout(), Duration::from_secs(3));
+    }
 
     #[test]
     fn data_is_flatten() {
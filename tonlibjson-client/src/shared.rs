@@ -1,37 +1,70 @@
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use tower::{Layer, Service};
 use tower::load::Load;
 use tokio::sync::{RwLock};
 
-#[derive(Default)]
-pub struct SharedLayer;
+/// Default time constant for the Peak-EWMA RTT decay, matching tower's own default.
+const DEFAULT_EWMA_DECAY: Duration = Duration::from_secs(1);
+
+/// Seed RTT used for a backend that hasn't completed a request yet. Without this, a cold
+/// backend's estimate starts at zero and looks like the best possible choice, inviting a
+/// thundering herd onto it; tower's own `PeakEwma` takes the same `default_rtt` knob.
+const DEFAULT_RTT_ESTIMATE: Duration = Duration::from_millis(50);
+
+pub struct SharedLayer {
+    decay: Duration,
+    default_rtt: Duration
+}
+
+impl Default for SharedLayer {
+    fn default() -> Self {
+        Self { decay: DEFAULT_EWMA_DECAY, default_rtt: DEFAULT_RTT_ESTIMATE }
+    }
+}
+
+impl SharedLayer {
+    pub fn new(decay: Duration, default_rtt: Duration) -> Self {
+        Self { decay, default_rtt }
+    }
+}
 
 impl<S> Layer<S> for SharedLayer {
     type Service = SharedService<S>;
 
     fn layer(&self, inner: S) -> Self::Service {
-        SharedService::new(inner)
+        SharedService::with_decay(inner, self.decay, self.default_rtt)
     }
 }
 
 pub struct SharedService<S> {
-    inner: Arc<RwLock<S>>
+    inner: Arc<RwLock<S>>,
+    load: Arc<PeakEwma>
 }
 
 impl<S> Clone for SharedService<S> {
     fn clone(&self) -> Self {
         Self {
-            inner: self.inner.clone()
+            inner: self.inner.clone(),
+            load: self.load.clone()
         }
     }
 }
 
 impl<S> SharedService<S> {
     pub fn new(inner: S) -> Self {
-        Self { inner: Arc::new(RwLock::new(inner)) }
+        Self::with_decay(inner, DEFAULT_EWMA_DECAY, DEFAULT_RTT_ESTIMATE)
+    }
+
+    pub fn with_decay(inner: S, decay: Duration, default_rtt: Duration) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(inner)),
+            load: Arc::new(PeakEwma::new(decay, default_rtt))
+        }
     }
 }
 
@@ -59,21 +92,98 @@ impl<S, Req> Service<Req> for SharedService<S>
         use futures::FutureExt;
 
         let client = Arc::clone(&self.inner);
+        let outstanding = PeakEwma::start(&self.load);
 
         async move {
             let mut guard = client.write().await;
             let r = guard.call(req);
             drop(guard);
 
-            r.await
+            let response = r.await;
+            outstanding.complete();
+
+            response
         }.boxed()
     }
 }
 
-impl<S> Load for SharedService<S> where S : Load {
-    type Metric = S::Metric;
+impl<S> Load for SharedService<S> {
+    type Metric = f64;
 
     fn load(&self) -> Self::Metric {
-        tokio::task::block_in_place(|| self.inner.blocking_read().load())
+        self.load.estimate()
+    }
+}
+
+struct RttEstimate {
+    update_at: Instant,
+    rtt: f64
+}
+
+/// Latency-aware load metric, mirroring tower's `PeakEwma`: the reported load is
+/// `rtt_estimate * (outstanding_requests + 1)`, where `rtt_estimate` decays towards the
+/// observed RTT over time constant `tau`, but jumps up immediately to any RTT that exceeds
+/// the current estimate so a newly-slow node is penalized right away.
+struct PeakEwma {
+    decay_ns: f64,
+    outstanding: AtomicIsize,
+    estimate: Mutex<RttEstimate>
+}
+
+impl PeakEwma {
+    fn new(tau: Duration, default_rtt: Duration) -> Self {
+        Self {
+            decay_ns: tau.as_nanos().max(1) as f64,
+            outstanding: AtomicIsize::new(0),
+            estimate: Mutex::new(RttEstimate { update_at: Instant::now(), rtt: default_rtt.as_nanos() as f64 })
+        }
+    }
+
+    /// Marks the start of a request and returns a guard that decrements `outstanding` when
+    /// dropped, whichever way the in-flight future ends: call `complete()` on the happy path to
+    /// also fold the observed RTT into the estimate, or simply let it drop (e.g. because the
+    /// future was cancelled by a timeout) to release the outstanding count without touching RTT.
+    fn start(self: &Arc<Self>) -> OutstandingGuard {
+        self.outstanding.fetch_add(1, Ordering::AcqRel);
+
+        OutstandingGuard { load: Arc::clone(self), start: Instant::now() }
+    }
+
+    fn estimate(&self) -> f64 {
+        let outstanding = self.outstanding.load(Ordering::Acquire).max(0) as f64;
+        let rtt = self.estimate.lock().unwrap().rtt;
+
+        rtt * (outstanding + 1.0)
+    }
+}
+
+struct OutstandingGuard {
+    load: Arc<PeakEwma>,
+    start: Instant
+}
+
+impl OutstandingGuard {
+    /// Records the observed RTT for this request. `outstanding` is still released by `Drop`,
+    /// which runs immediately afterwards since this method takes `self` by value.
+    fn complete(self) {
+        let now = Instant::now();
+        let observed_ns = now.saturating_duration_since(self.start).as_nanos() as f64;
+
+        let mut guard = self.load.estimate.lock().unwrap();
+        let elapsed_ns = now.saturating_duration_since(guard.update_at).as_nanos() as f64;
+        let w = (-elapsed_ns / self.load.decay_ns).exp();
+
+        guard.rtt = if observed_ns > guard.rtt {
+            observed_ns
+        } else {
+            guard.rtt * w + observed_ns * (1.0 - w)
+        };
+        guard.update_at = now;
+    }
+}
+
+impl Drop for OutstandingGuard {
+    fn drop(&mut self) {
+        self.load.outstanding.fetch_sub(1, Ordering::AcqRel);
     }
 }
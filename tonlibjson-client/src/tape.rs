@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::Write;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use anyhow::anyhow;
+use tokio::sync::Mutex as AsyncMutex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tower::{Layer, Service};
+use crate::request::Request;
+
+/// A single recorded request/response pair, newline-delimited JSON on disk.
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    request: Value,
+    #[serde(rename = "timeout_ms")]
+    timeout_ms: u64,
+    response: Value
+}
+
+/// Appends every `Request`/`Response` pair that passes through it to a tape file, so the
+/// traffic can later be served offline by a [`ReplayService`].
+pub struct RecordLayer {
+    tape: Arc<Mutex<std::fs::File>>
+}
+
+impl RecordLayer {
+    pub fn new(tape_path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(tape_path)?;
+
+        Ok(Self { tape: Arc::new(Mutex::new(file)) })
+    }
+}
+
+impl<S> Layer<S> for RecordLayer {
+    type Service = RecordService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RecordService { inner, tape: Arc::clone(&self.tape) }
+    }
+}
+
+pub struct RecordService<S> {
+    inner: S,
+    tape: Arc<Mutex<std::fs::File>>
+}
+
+impl<S> Clone for RecordService<S> where S : Clone {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone(), tape: Arc::clone(&self.tape) }
+    }
+}
+
+impl<S> Service<Request> for RecordService<S>
+    where S : Service<Request, Response = Value, Error = anyhow::Error> + Send, S::Future : Send + 'static {
+    type Response = Value;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        use futures::FutureExt;
+
+        let tape = Arc::clone(&self.tape);
+        let recorded_request = req.data.clone();
+        let timeout_ms = req.timeout.as_millis() as u64;
+        let future = self.inner.call(req);
+
+        async move {
+            let response = future.await?;
+
+            let interaction = Interaction { request: recorded_request, timeout_ms, response: response.clone() };
+            let line = serde_json::to_string(&interaction)?;
+
+            let mut file = tape.lock().map_err(|e| anyhow!(e.to_string()))?;
+            writeln!(file, "{line}")?;
+
+            Ok(response)
+        }.boxed()
+    }
+}
+
+/// What a [`ReplayService`] does when a request isn't found on the tape.
+pub enum ReplayMode<S> {
+    /// Return an error; useful for asserting that a test only exercises recorded traffic.
+    ErrorOnMiss,
+    /// Fall through to a real client and append the new interaction to the tape. The fallback
+    /// service is expected to already be wrapped in a [`RecordLayer`] pointed at the same tape.
+    RecordOnMiss(S)
+}
+
+/// Serves responses from a previously recorded tape, keyed by the serialized request body
+/// rather than the random `@extra` id, so tests can replay recorded mainnet traffic
+/// deterministically without a live liteserver. The in-memory tape is mutable so that, in
+/// [`ReplayMode::RecordOnMiss`], an interaction recorded during this run is immediately
+/// available for any later request with the same body rather than falling through to the
+/// real client again.
+pub struct ReplayService<S> {
+    tape: Arc<RwLock<HashMap<Value, Value>>>,
+    mode: Arc<AsyncMutex<ReplayMode<S>>>
+}
+
+impl<S> Clone for ReplayService<S> {
+    fn clone(&self) -> Self {
+        Self { tape: Arc::clone(&self.tape), mode: Arc::clone(&self.mode) }
+    }
+}
+
+impl<S> ReplayService<S> {
+    pub fn load(tape_path: impl AsRef<Path>, mode: ReplayMode<S>) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(tape_path)?;
+        let mut tape = HashMap::new();
+
+        for line in contents.lines().filter(|l| !l.is_empty()) {
+            let interaction: Interaction = serde_json::from_str(line)?;
+            tape.insert(interaction.request, interaction.response);
+        }
+
+        Ok(Self { tape: Arc::new(RwLock::new(tape)), mode: Arc::new(AsyncMutex::new(mode)) })
+    }
+}
+
+impl<S> Service<Request> for ReplayService<S>
+    where S : Service<Request, Response = Value, Error = anyhow::Error> + Send + 'static, S::Future : Send {
+    type Response = Value;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match self.mode.try_lock() {
+            Ok(mut mode) => match &mut *mode {
+                ReplayMode::ErrorOnMiss => Poll::Ready(Ok(())),
+                ReplayMode::RecordOnMiss(client) => client.poll_ready(cx)
+            },
+            Err(_) => {
+                cx.waker().wake_by_ref();
+
+                Poll::Pending
+            }
+        }
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        use futures::FutureExt;
+
+        if let Some(response) = self.tape.read().unwrap().get(&req.data) {
+            let response = response.clone();
+
+            return async move { Ok(response) }.boxed();
+        }
+
+        let tape = Arc::clone(&self.tape);
+        let mode = Arc::clone(&self.mode);
+        let recorded_request = req.data.clone();
+
+        async move {
+            let response = match &mut *mode.lock().await {
+                ReplayMode::ErrorOnMiss => Err(anyhow!("no recorded interaction for request {}", req.data)),
+                ReplayMode::RecordOnMiss(client) => client.call(req).await
+            }?;
+
+            tape.write().unwrap().insert(recorded_request, response.clone());
+
+            Ok(response)
+        }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use tower::{Layer, Service, ServiceExt, service_fn};
+    use crate::request::Request;
+    use super::{RecordLayer, ReplayMode, ReplayService};
+
+    fn tape_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tonlibjson-client-tape-test-{name}-{:?}.ndjson", std::thread::current().id()))
+    }
+
+    #[tokio::test]
+    async fn records_and_replays_an_interaction() {
+        let path = tape_path("round-trip");
+        let _ = std::fs::remove_file(&path);
+
+        let inner = service_fn(|_req: Request| async { Ok::<_, anyhow::Error>(json!({ "ok": true })) });
+        let mut recorder = RecordLayer::new(&path).unwrap().layer(inner);
+
+        let request = Request::with_timeout(json!({ "@type": "liteServer.getMasterchainInfo" }), Duration::from_secs(3)).unwrap();
+        recorder.ready().await.unwrap().call(request).await.unwrap();
+
+        let mut replay = ReplayService::<tower::util::ServiceFn<fn(Request) -> futures::future::Ready<anyhow::Result<serde_json::Value>>>>::load(&path, ReplayMode::ErrorOnMiss).unwrap();
+        let request = Request::with_timeout(json!({ "@type": "liteServer.getMasterchainInfo" }), Duration::from_secs(3)).unwrap();
+        let response = replay.ready().await.unwrap().call(request).await.unwrap();
+
+        assert_eq!(response, json!({ "ok": true }));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn error_on_miss_rejects_unrecorded_request() {
+        let path = tape_path("error-on-miss");
+        std::fs::write(&path, "").unwrap();
+
+        let mut replay = ReplayService::<tower::util::ServiceFn<fn(Request) -> futures::future::Ready<anyhow::Result<serde_json::Value>>>>::load(&path, ReplayMode::ErrorOnMiss).unwrap();
+        let request = Request::with_timeout(json!({ "@type": "liteServer.getMasterchainInfo" }), Duration::from_secs(3)).unwrap();
+        let result = replay.ready().await.unwrap().call(request).await;
+
+        assert!(result.is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn record_on_miss_falls_through_and_caches_for_next_time() {
+        let path = tape_path("record-on-miss");
+        std::fs::write(&path, "").unwrap();
+
+        let calls = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let calls_ref = calls.clone();
+        let fallback = service_fn(move |_req: Request| {
+            calls_ref.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+            async { Ok::<_, anyhow::Error>(json!({ "live": true })) }
+        });
+        let fallback = RecordLayer::new(&path).unwrap().layer(fallback);
+
+        let mut replay = ReplayService::load(&path, ReplayMode::RecordOnMiss(fallback)).unwrap();
+
+        let request = || Request::with_timeout(json!({ "@type": "liteServer.getMasterchainInfo" }), Duration::from_secs(3)).unwrap();
+
+        let first = replay.ready().await.unwrap().call(request()).await.unwrap();
+        let second = replay.ready().await.unwrap().call(request()).await.unwrap();
+
+        assert_eq!(first, json!({ "live": true }));
+        assert_eq!(second, json!({ "live": true }));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1, "second call should be served from the freshly-recorded tape, not the live client");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
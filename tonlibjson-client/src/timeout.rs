@@ -0,0 +1,116 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use anyhow::anyhow;
+use tower::Layer;
+use tower::Service;
+use telemetry::{method_attribute, Metrics};
+use crate::filter::method_name;
+use crate::request::Request;
+
+#[derive(Debug)]
+pub struct TimeoutElapsed;
+
+impl std::fmt::Display for TimeoutElapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "request timed out")
+    }
+}
+
+impl std::error::Error for TimeoutElapsed {}
+
+#[derive(Default)]
+pub struct TimeoutLayer;
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = TimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TimeoutService::new(inner)
+    }
+}
+
+pub struct TimeoutService<S> {
+    inner: S
+}
+
+impl<S> TimeoutService<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+impl<S> Clone for TimeoutService<S> where S : Clone {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<S> Service<Request> for TimeoutService<S>
+    where S : Service<Request, Error = anyhow::Error> + Send, S::Future : Send + 'static, S::Response : Send {
+    type Response = S::Response;
+    type Error = anyhow::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        use futures::FutureExt;
+
+        let timeout = req.timeout;
+        let method = method_name(&req).unwrap_or("unknown").to_string();
+        let start = Instant::now();
+        let call = self.inner.call(req);
+
+        async move {
+            let result = match tokio::time::timeout(timeout, call).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow!(TimeoutElapsed))
+            };
+
+            Metrics::get().liteserver_call_latency.record(start.elapsed().as_secs_f64(), &method_attribute(&method));
+
+            result
+        }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use serde_json::json;
+    use tower::{Layer, Service, ServiceExt, service_fn};
+    use crate::request::Request;
+    use super::TimeoutLayer;
+
+    #[tokio::test]
+    async fn times_out_slow_inner_service() {
+        let inner = service_fn(|_req: Request| async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+
+            Ok::<_, anyhow::Error>(json!({}))
+        });
+        let mut service = TimeoutLayer.layer(inner);
+
+        let request = Request::with_timeout(json!({}), Duration::from_millis(10)).unwrap();
+        let result = service.ready().await.unwrap().call(request).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn passes_through_fast_inner_service() {
+        let inner = service_fn(|_req: Request| async {
+            Ok::<_, anyhow::Error>(json!({ "ok": true }))
+        });
+        let mut service = TimeoutLayer.layer(inner);
+
+        let request = Request::with_timeout(json!({}), Duration::from_secs(3)).unwrap();
+        let result = service.ready().await.unwrap().call(request).await;
+
+        assert_eq!(result.unwrap(), json!({ "ok": true }));
+    }
+}
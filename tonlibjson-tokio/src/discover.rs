@@ -2,23 +2,36 @@ use crate::client::Client;
 use crate::make::ClientFactory;
 use crate::ton_config::load_ton_config;
 use async_stream::try_stream;
+use rand::Rng;
 use reqwest::Url;
 use std::time::Duration;
 use std::{
     pin::Pin,
     task::{Context, Poll},
 };
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use tokio_stream::Stream;
 use tower::discover::Change;
 use tower::limit::ConcurrencyLimit;
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 use crate::ton_config::Liteserver;
 use tower::ServiceExt;
 use tower::Service;
+use telemetry::Metrics;
 
 type DiscoverResult<K, S, E> = Result<Change<K, S>, E>;
 
+const DISCOVERY_BACKOFF_BASE: Duration = Duration::from_secs(1);
+const DISCOVERY_BACKOFF_CAP: Duration = Duration::from_secs(64);
+
+/// Number of consecutive failed health-check probes against an already-active liteserver
+/// before it's evicted.
+const LITESERVER_FAILURE_THRESHOLD: u32 = 3;
+
+/// Active liteservers are only re-probed every `N`th tick rather than on every tick, so a large
+/// healthy fleet isn't reconnected-to just to confirm it's still there.
+const HEALTH_CHECK_EVERY_N_TICKS: u32 = 5;
+
 pub struct DynamicServiceStream {
     changes: Pin<Box<dyn Stream<Item = Result<Change<String, ConcurrencyLimit<Client>>, anyhow::Error>> + Send>>,
 }
@@ -26,7 +39,10 @@ pub struct DynamicServiceStream {
 impl DynamicServiceStream {
     pub(crate) fn new(url: Url, period: Duration) -> anyhow::Result<Self> {
         let mut interval = tokio::time::interval(period);
-        let mut liteservers = HashSet::new();
+        let mut liteservers: HashSet<Liteserver> = HashSet::new();
+        let mut failures: HashMap<String, u32> = HashMap::new();
+        let mut attempt: u32 = 0;
+        let mut tick: u32 = 0;
         let mut factory = ClientFactory::default();
 
         // TODO[akostylev0] refac
@@ -35,28 +51,100 @@ impl DynamicServiceStream {
                 interval.tick().await;
 
                 info!("tick service discovery");
-                let config = load_ton_config(url.clone()).await?;
+                let config = match load_ton_config(url.clone()).await {
+                    Ok(config) => {
+                        attempt = 0;
+
+                        config
+                    },
+                    Err(e) => {
+                        let delay = discovery_backoff(attempt);
+                        attempt = attempt.saturating_add(1);
+
+                        error!(error = ?e, retry_in = ?delay, "failed to load ton config, keeping existing liteservers");
+
+                        tokio::time::sleep(delay).await;
+
+                        continue;
+                    }
+                };
                 let liteserver_new: HashSet<Liteserver> = HashSet::from_iter(config.liteservers.iter().cloned());
 
+                // `liteservers` tracks liteservers we've actually connected to, not merely the
+                // last-seen config, so a liteserver whose connect attempt fails keeps showing up
+                // as an insert candidate on subsequent ticks instead of being silently treated as
+                // active.
                 let liteservers_remove = liteservers.difference(&liteserver_new).collect::<Vec<&Liteserver>>();
                 let liteservers_insert = liteserver_new.difference(&liteservers).collect::<Vec<&Liteserver>>();
 
                 debug!("Discovered {} liteservers, remove {}, insert {}", liteserver_new.len(), liteservers_remove.len(), liteservers_insert.len());
 
-                for ls in liteservers_remove {
+                let mut active = liteservers.clone();
+
+                for ls in &liteservers_remove {
                     debug!("remove {:?}", ls.id());
+                    failures.remove(&ls.id());
+                    active.remove(*ls);
+                    Metrics::get().liteservers_removed.add(1, &[]);
+                    Metrics::get().liteservers_discovered.add(-1, &[]);
                     yield Change::Remove(ls.id());
                 }
 
-                for ls in liteservers_insert {
+                for ls in &liteservers_insert {
                     debug!("insert {:?}", ls.id());
 
-                    if let Ok(client) = factory.ready().await?.call(config.with_liteserver(ls)).await {
-                        yield Change::Insert(ls.id(), client);
+                    match factory.ready().await?.call(config.with_liteserver(ls)).await {
+                        Ok(client) => {
+                            failures.remove(&ls.id());
+                            active.insert((*ls).clone());
+                            Metrics::get().liteservers_inserted.add(1, &[]);
+                            Metrics::get().liteservers_discovered.add(1, &[]);
+                            yield Change::Insert(ls.id(), client);
+                        },
+                        Err(e) => {
+                            let count = failures.entry(ls.id()).or_insert(0);
+                            *count += 1;
+
+                            warn!(error = ?e, "failed to connect to liteserver {:?} ({} consecutive failures)", ls.id(), count);
+                        }
                     }
                 }
 
-                liteservers = liteserver_new.clone();
+                liteservers = active;
+                tick = tick.wrapping_add(1);
+
+                // Already-active liteservers are re-probed on a slower cadence than the config
+                // poll itself, not every tick: reconnecting to every healthy node just to "ping"
+                // it on every tick would open a fresh connection to the whole fleet far more
+                // often than needed. A liteserver is evicted once its probe has failed
+                // `LITESERVER_FAILURE_THRESHOLD` times in a row, and re-`Insert`ed the next time
+                // it answers, so recovery doesn't require waiting for a config change.
+                if tick % HEALTH_CHECK_EVERY_N_TICKS == 0 {
+                    let already_active = liteservers.iter().cloned().collect::<Vec<Liteserver>>();
+
+                    for ls in &already_active {
+                        match factory.ready().await?.call(config.with_liteserver(ls)).await {
+                            Ok(_) => {
+                                failures.remove(&ls.id());
+                            },
+                            Err(e) => {
+                                let count = failures.entry(ls.id()).or_insert(0);
+                                *count += 1;
+
+                                warn!(error = ?e, "health check failed for liteserver {:?} ({} consecutive failures)", ls.id(), count);
+
+                                if *count >= LITESERVER_FAILURE_THRESHOLD {
+                                    debug!("evicting unhealthy liteserver {:?}", ls.id());
+                                    failures.remove(&ls.id());
+                                    liteservers.remove(ls);
+                                    Metrics::get().liteservers_removed.add(1, &[]);
+                                    Metrics::get().liteservers_discovered.add(-1, &[]);
+                                    yield Change::Remove(ls.id());
+                                }
+                            }
+                        }
+                    }
+                }
             }
         };
 
@@ -66,6 +154,32 @@ impl DynamicServiceStream {
     }
 }
 
+fn discovery_backoff(attempt: u32) -> Duration {
+    let exp = DISCOVERY_BACKOFF_BASE.saturating_mul(1u32 << attempt.min(6));
+    let capped = exp.min(DISCOVERY_BACKOFF_CAP);
+    let jitter = rand::thread_rng().gen_range(0.5..1.0);
+
+    capped.mul_f64(jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{discovery_backoff, DISCOVERY_BACKOFF_CAP};
+
+    #[test]
+    fn backoff_grows_with_attempt() {
+        assert!(discovery_backoff(0) <= discovery_backoff(1));
+        assert!(discovery_backoff(1) <= discovery_backoff(2));
+    }
+
+    #[test]
+    fn backoff_never_exceeds_the_cap() {
+        for attempt in [6, 10, 20, u32::MAX] {
+            assert!(discovery_backoff(attempt) <= DISCOVERY_BACKOFF_CAP);
+        }
+    }
+}
+
 impl Stream for DynamicServiceStream {
     type Item = DiscoverResult<String, ConcurrencyLimit<Client>, anyhow::Error>;
 
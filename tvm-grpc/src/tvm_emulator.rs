@@ -1,21 +1,47 @@
+use std::collections::HashMap;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use anyhow::anyhow;
 use async_stream::stream;
 use futures::{StreamExt, Stream};
+use telemetry::{method_attribute, Metrics};
 use tonic::{async_trait, Request, Response, Status, Streaming};
 use tracing::{error, Level, span};
-use crate::tvm::tvm_emulator_request::Request::{Prepare, RunGetMethod, SendExternalMessage, SendInternalMessage, SetC7, SetGasLimit, SetLibraries};
-use crate::tvm::tvm_emulator_response::Response::{PrepareResponse, RunGetMethodResponse, SendExternalMessageResponse, SendInternalMessageResponse, SetC7Response, SetGasLimitResponse, SetLibrariesResponse};
+use crate::tvm::tvm_emulator_request::Request::{DropSession, Prepare, RunGetMethod, SendExternalMessage, SendInternalMessage, SetC7, SetGasLimit, SetLibraries};
+use crate::tvm::tvm_emulator_response::Response::{DropSessionResponse, PrepareResponse, RunGetMethodResponse, SendExternalMessageResponse, SendInternalMessageResponse, SetC7Response, SetGasLimitResponse, SetLibrariesResponse};
 use crate::tvm::tvm_emulator_service_server::TvmEmulatorService as BaseTvmEmulatorService;
-use crate::tvm::{TvmEmulatorPrepareRequest, TvmEmulatorPrepareResponse, TvmEmulatorRequest, TvmEmulatorResponse, TvmEmulatorRunGetMethodRequest, TvmEmulatorRunGetMethodResponse, TvmEmulatorSendExternalMessageRequest, TvmEmulatorSendExternalMessageResponse, TvmEmulatorSendInternalMessageRequest, TvmEmulatorSendInternalMessageResponse, TvmEmulatorSetC7Request, TvmEmulatorSetC7Response, TvmEmulatorSetGasLimitRequest, TvmEmulatorSetGasLimitResponse, TvmEmulatorSetLibrariesRequest, TvmEmulatorSetLibrariesResponse, TvmResult};
+use crate::tvm::{TvmEmulatorDropSessionRequest, TvmEmulatorDropSessionResponse, TvmEmulatorPrepareRequest, TvmEmulatorPrepareResponse, TvmEmulatorRequest, TvmEmulatorResponse, TvmEmulatorRunGetMethodRequest, TvmEmulatorRunGetMethodResponse, TvmEmulatorSendExternalMessageRequest, TvmEmulatorSendExternalMessageResponse, TvmEmulatorSendInternalMessageRequest, TvmEmulatorSendInternalMessageResponse, TvmEmulatorSetC7Request, TvmEmulatorSetC7Response, TvmEmulatorSetGasLimitRequest, TvmEmulatorSetGasLimitResponse, TvmEmulatorSetLibrariesRequest, TvmEmulatorSetLibrariesResponse, TvmResult};
 
 #[derive(Debug, Default)]
 pub struct TvmEmulatorService;
 
+// NB: each request variant now carries a `session_id: u64` (see tvm.proto) that selects which
+// emulator instance it addresses, so a single bidirectional stream can multiplex several
+// concurrently-running contracts instead of being pinned to one.
+//
+// The map of sessions is only locked for the brief lookup/insert/remove itself, never for the
+// duration of an emulator call: each session gets its own `Mutex`, so two sessions can run their
+// (blocking) emulator calls concurrently instead of serializing behind one lock for the whole
+// stream.
 #[derive(Default)]
 struct State {
-    emulator: Option<tonlibjson_sys::TvmEmulator>
+    sessions: Mutex<HashMap<u64, Arc<Mutex<tonlibjson_sys::TvmEmulator>>>>
+}
+
+impl State {
+    fn session(&self, session_id: u64) -> anyhow::Result<Arc<Mutex<tonlibjson_sys::TvmEmulator>>> {
+        self.sessions.lock().unwrap().get(&session_id).cloned()
+            .ok_or_else(|| anyhow!("session {session_id} is not initialized"))
+    }
+
+    fn insert(&self, session_id: u64, emulator: tonlibjson_sys::TvmEmulator) {
+        self.sessions.lock().unwrap().insert(session_id, Arc::new(Mutex::new(emulator)));
+    }
+
+    fn remove(&self, session_id: u64) -> bool {
+        self.sessions.lock().unwrap().remove(&session_id).is_some()
+    }
 }
 
 #[async_trait]
@@ -26,7 +52,7 @@ impl BaseTvmEmulatorService for TvmEmulatorService {
         let stream = request.into_inner();
 
         let output = stream! {
-            let state = Arc::new(Mutex::new(State::default()));
+            let state = Arc::new(State::default());
 
             for await msg in stream {
                 match msg {
@@ -34,19 +60,19 @@ impl BaseTvmEmulatorService for TvmEmulatorService {
                         let span = span!(Level::TRACE, "tvm emulator request", request_id=request_id);
                         let _guard = span.enter();
                         let state = Arc::clone(&state);
+                        let method = req.method_name();
+                        let start = Instant::now();
 
                         let response = tokio::task::spawn_blocking(move || {
-                            let mut state = state.lock()
-                                .map_err(|e| anyhow!(e.to_string()))?;
-
                             match req {
-                                Prepare(req) => prepare_emu(&mut state, req).map(PrepareResponse),
-                                RunGetMethod(req) => run_get_method(&mut state, req).map(RunGetMethodResponse),
-                                SendExternalMessage(req) => send_external_message(&mut state, req).map(SendExternalMessageResponse),
-                                SendInternalMessage(req) => send_internal_message(&mut state, req).map(SendInternalMessageResponse),
-                                SetLibraries(req) => set_libraries(&mut state, req).map(SetLibrariesResponse),
-                                SetGasLimit(req) => set_gas_limit(&mut state, req).map(SetGasLimitResponse),
-                                SetC7(req) => set_c7(&mut state, req).map(SetC7Response)
+                                Prepare(req) => prepare_emu(&state, req).map(PrepareResponse),
+                                RunGetMethod(req) => run_get_method(&state, req).map(RunGetMethodResponse),
+                                SendExternalMessage(req) => send_external_message(&state, req).map(SendExternalMessageResponse),
+                                SendInternalMessage(req) => send_internal_message(&state, req).map(SendInternalMessageResponse),
+                                SetLibraries(req) => set_libraries(&state, req).map(SetLibrariesResponse),
+                                SetGasLimit(req) => set_gas_limit(&state, req).map(SetGasLimitResponse),
+                                SetC7(req) => set_c7(&state, req).map(SetC7Response),
+                                DropSession(req) => drop_session(&state, req).map(DropSessionResponse)
                         }}).await
                              .map_err(|e| {
                                 error!(error = ?e);
@@ -54,6 +80,9 @@ impl BaseTvmEmulatorService for TvmEmulatorService {
                                 Status::internal(e.to_string())
                             })?;
 
+                        Metrics::get().emulator_calls.add(1, &method_attribute(method));
+                        Metrics::get().emulator_call_duration.record(start.elapsed().as_secs_f64(), &method_attribute(method));
+
                         yield response
                             .map(|r| TvmEmulatorResponse { request_id, response: Some(r) })
                             .map_err(|e| {
@@ -80,29 +109,33 @@ impl BaseTvmEmulatorService for TvmEmulatorService {
     }
 }
 
-fn prepare_emu(state: &mut State, req: TvmEmulatorPrepareRequest) -> anyhow::Result<TvmEmulatorPrepareResponse> {
-    state.emulator.replace(tonlibjson_sys::TvmEmulator::new(&req.code_boc, &req.data_boc, req.vm_log_verbosity)?);
+fn prepare_emu(state: &State, req: TvmEmulatorPrepareRequest) -> anyhow::Result<TvmEmulatorPrepareResponse> {
+    let emulator = tonlibjson_sys::TvmEmulator::new(&req.code_boc, &req.data_boc, req.vm_log_verbosity)?;
+    state.insert(req.session_id, emulator);
 
     Ok(TvmEmulatorPrepareResponse { success: true })
 }
 
-fn run_get_method(state: &mut State, req: TvmEmulatorRunGetMethodRequest) -> anyhow::Result<TvmEmulatorRunGetMethodResponse> {
-    let Some(emu) = state.emulator.as_ref() else {
-        return Err(anyhow!("emulator not initialized"));
-    };
+fn run_get_method(state: &State, req: TvmEmulatorRunGetMethodRequest) -> anyhow::Result<TvmEmulatorRunGetMethodResponse> {
+    let emu = state.session(req.session_id)?;
+    let emu = emu.lock().map_err(|e| anyhow!(e.to_string()))?;
 
     let response = emu.run_get_method(req.method_id, &req.stack_boc)?;
     tracing::trace!(method="run_get_method", "{}", response);
 
     let response = serde_json::from_str::<TvmResult<TvmEmulatorRunGetMethodResponse>>(response)?;
+    let response: anyhow::Result<TvmEmulatorRunGetMethodResponse> = response.into();
 
-    response.into()
+    if let Ok(ref response) = response {
+        Metrics::get().emulator_gas_consumed.record(response.gas_used, &[]);
+    }
+
+    response
 }
 
-fn send_external_message(state: &mut State, req: TvmEmulatorSendExternalMessageRequest) -> anyhow::Result<TvmEmulatorSendExternalMessageResponse> {
-    let Some(emu) = state.emulator.as_ref() else {
-        return Err(anyhow!("emulator not initialized"));
-    };
+fn send_external_message(state: &State, req: TvmEmulatorSendExternalMessageRequest) -> anyhow::Result<TvmEmulatorSendExternalMessageResponse> {
+    let emu = state.session(req.session_id)?;
+    let emu = emu.lock().map_err(|e| anyhow!(e.to_string()))?;
 
     let response = emu.send_external_message(&req.message_body_boc)?;
     tracing::trace!(method="send_external_message", "{}", response);
@@ -112,10 +145,9 @@ fn send_external_message(state: &mut State, req: TvmEmulatorSendExternalMessageR
     response.into()
 }
 
-fn send_internal_message(state: &mut State, req: TvmEmulatorSendInternalMessageRequest) -> anyhow::Result<TvmEmulatorSendInternalMessageResponse> {
-    let Some(emu) = state.emulator.as_ref() else {
-        return Err(anyhow!("emulator not initialized"));
-    };
+fn send_internal_message(state: &State, req: TvmEmulatorSendInternalMessageRequest) -> anyhow::Result<TvmEmulatorSendInternalMessageResponse> {
+    let emu = state.session(req.session_id)?;
+    let emu = emu.lock().map_err(|e| anyhow!(e.to_string()))?;
 
     let response = emu.send_internal_message(&req.message_body_boc, req.amount)?;
     tracing::trace!(method="send_internal_message", "{}", response);
@@ -125,10 +157,9 @@ fn send_internal_message(state: &mut State, req: TvmEmulatorSendInternalMessageR
     response.into()
 }
 
-fn set_libraries(state: &mut State, req: TvmEmulatorSetLibrariesRequest) -> anyhow::Result<TvmEmulatorSetLibrariesResponse> {
-    let Some(emu) = state.emulator.as_ref() else {
-        return Err(anyhow!("emulator not initialized"));
-    };
+fn set_libraries(state: &State, req: TvmEmulatorSetLibrariesRequest) -> anyhow::Result<TvmEmulatorSetLibrariesResponse> {
+    let emu = state.session(req.session_id)?;
+    let emu = emu.lock().map_err(|e| anyhow!(e.to_string()))?;
 
     let response = emu.set_libraries(&req.libs_boc)?;
     tracing::trace!(method="set_libraries", "{}", response);
@@ -136,10 +167,9 @@ fn set_libraries(state: &mut State, req: TvmEmulatorSetLibrariesRequest) -> anyh
     Ok(TvmEmulatorSetLibrariesResponse { success: response })
 }
 
-fn set_gas_limit(state: &mut State, req: TvmEmulatorSetGasLimitRequest) -> anyhow::Result<TvmEmulatorSetGasLimitResponse> {
-    let Some(emu) = state.emulator.as_ref() else {
-        return Err(anyhow!("emulator not initialized"));
-    };
+fn set_gas_limit(state: &State, req: TvmEmulatorSetGasLimitRequest) -> anyhow::Result<TvmEmulatorSetGasLimitResponse> {
+    let emu = state.session(req.session_id)?;
+    let emu = emu.lock().map_err(|e| anyhow!(e.to_string()))?;
 
     let response = emu.set_gas_limit(req.gas_limit);
     tracing::trace!(method="set_gas_limit", "{}", response);
@@ -147,13 +177,102 @@ fn set_gas_limit(state: &mut State, req: TvmEmulatorSetGasLimitRequest) -> anyho
     Ok(TvmEmulatorSetGasLimitResponse { success: response })
 }
 
-fn set_c7(state: &mut State, req: TvmEmulatorSetC7Request) -> anyhow::Result<TvmEmulatorSetC7Response> {
-    let Some(emu) = state.emulator.as_ref() else {
-        return Err(anyhow!("emulator not initialized"));
-    };
+fn set_c7(state: &State, req: TvmEmulatorSetC7Request) -> anyhow::Result<TvmEmulatorSetC7Response> {
+    let emu = state.session(req.session_id)?;
+    let emu = emu.lock().map_err(|e| anyhow!(e.to_string()))?;
 
     let response = emu.set_c7(&req.address, req.unixtime, req.balance, &req.rand_seed_hex, &req.config)?;
     tracing::trace!(method="set_c7", "{}", response);
 
     Ok(TvmEmulatorSetC7Response { success: response })
-}
\ No newline at end of file
+}
+
+fn drop_session(state: &State, req: TvmEmulatorDropSessionRequest) -> anyhow::Result<TvmEmulatorDropSessionResponse> {
+    let existed = state.remove(req.session_id);
+
+    Ok(TvmEmulatorDropSessionResponse { success: existed })
+}
+
+trait RequestMethodName {
+    fn method_name(&self) -> &'static str;
+}
+
+impl RequestMethodName for crate::tvm::tvm_emulator_request::Request {
+    fn method_name(&self) -> &'static str {
+        match self {
+            Prepare(_) => "prepare",
+            RunGetMethod(_) => "run_get_method",
+            SendExternalMessage(_) => "send_external_message",
+            SendInternalMessage(_) => "send_internal_message",
+            SetLibraries(_) => "set_libraries",
+            SetGasLimit(_) => "set_gas_limit",
+            SetC7(_) => "set_c7",
+            DropSession(_) => "drop_session"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Canonical "empty cell" BOC (a single cell with no data/refs), used here purely to exercise
+    // the per-session bookkeeping in `State` rather than any real contract logic.
+    const EMPTY_CELL_BOC: &str = "te6cckEBAQEAAgAAAEysuc0=";
+
+    fn prepare_request(session_id: u64) -> TvmEmulatorPrepareRequest {
+        TvmEmulatorPrepareRequest {
+            session_id,
+            code_boc: EMPTY_CELL_BOC.to_string(),
+            data_boc: EMPTY_CELL_BOC.to_string(),
+            vm_log_verbosity: 0
+        }
+    }
+
+    #[test]
+    fn sessions_are_isolated_by_session_id() {
+        let state = State::default();
+
+        prepare_emu(&state, prepare_request(1)).unwrap();
+        prepare_emu(&state, prepare_request(2)).unwrap();
+
+        let session_one = state.session(1).unwrap();
+        let session_two = state.session(2).unwrap();
+
+        assert!(!Arc::ptr_eq(&session_one, &session_two), "distinct session ids must not share an emulator instance");
+    }
+
+    #[test]
+    fn run_get_method_errors_for_unknown_session() {
+        let state = State::default();
+
+        let result = run_get_method(&state, TvmEmulatorRunGetMethodRequest {
+            session_id: 42,
+            method_id: 0,
+            stack_boc: EMPTY_CELL_BOC.to_string()
+        });
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn drop_session_frees_the_entry() {
+        let state = State::default();
+        prepare_emu(&state, prepare_request(1)).unwrap();
+        assert!(state.session(1).is_ok());
+
+        let response = drop_session(&state, TvmEmulatorDropSessionRequest { session_id: 1 }).unwrap();
+
+        assert!(response.success);
+        assert!(state.session(1).is_err());
+    }
+
+    #[test]
+    fn drop_session_on_unknown_session_reports_failure() {
+        let state = State::default();
+
+        let response = drop_session(&state, TvmEmulatorDropSessionRequest { session_id: 7 }).unwrap();
+
+        assert!(!response.success);
+    }
+}